@@ -0,0 +1,82 @@
+use aes_gcm::aead::generic_array::GenericArray;
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use std::error::Error;
+
+const KDF_ROUNDS: u32 = 100_000;
+pub const NONCE_LEN: usize = 12;
+// Random per-encryption salt length. Prepended to the output stream ahead of
+// the nonce so a single precomputed dictionary can't target every payload
+// this tool ever produces.
+pub const SALT_LEN: usize = 16;
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2::<hmac::Hmac<sha2::Sha256>>(passphrase.as_bytes(), salt, KDF_ROUNDS, &mut key);
+    key
+}
+
+// Encrypt `data` with a key derived from `passphrase` and a fresh random
+// salt, returning that salt, followed by the 12-byte nonce, followed by
+// ciphertext and auth tag.
+pub fn encrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut salt = [0u8; SALT_LEN];
+    getrandom::getrandom(&mut salt)?;
+    let key = derive_key(passphrase, &salt);
+    let cipher = Aes256Gcm::new(GenericArray::from_slice(&key));
+
+    let mut nonce_bytes = [0u8; NONCE_LEN];
+    getrandom::getrandom(&mut nonce_bytes)?;
+    let nonce = Nonce::from_slice(&nonce_bytes);
+
+    let ciphertext = cipher
+        .encrypt(nonce, data)
+        .map_err(|_| "Error: encryption failed.")?;
+
+    let mut out = Vec::with_capacity(SALT_LEN + NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce_bytes);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+// Decrypt a salt-and-nonce-prefixed ciphertext produced by `encrypt`.
+pub fn decrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    if data.len() < SALT_LEN + NONCE_LEN {
+        return Err("Error: wrong passphrase or corrupted carrier.".into());
+    }
+    let (salt, rest) = data.split_at(SALT_LEN);
+    let (nonce_bytes, ciphertext) = rest.split_at(NONCE_LEN);
+    let key = derive_key(passphrase, salt);
+    let cipher = Aes256Gcm::new(GenericArray::from_slice(&key));
+    let nonce = Nonce::from_slice(nonce_bytes);
+
+    cipher
+        .decrypt(nonce, ciphertext)
+        .map_err(|_| "Error: wrong passphrase or corrupted carrier.".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_with_the_right_passphrase() {
+        let ciphertext = encrypt(b"hidden message", "correct horse").unwrap();
+        let plaintext = decrypt(&ciphertext, "correct horse").unwrap();
+        assert_eq!(plaintext, b"hidden message");
+    }
+
+    #[test]
+    fn rejects_the_wrong_passphrase() {
+        let ciphertext = encrypt(b"hidden message", "correct horse").unwrap();
+        assert!(decrypt(&ciphertext, "wrong horse").is_err());
+    }
+
+    #[test]
+    fn uses_a_different_salt_each_time() {
+        let a = encrypt(b"same data", "same passphrase").unwrap();
+        let b = encrypt(b"same data", "same passphrase").unwrap();
+        assert_ne!(&a[..SALT_LEN], &b[..SALT_LEN], "salt should be freshly random per encryption");
+    }
+}
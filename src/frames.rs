@@ -0,0 +1,303 @@
+use image::{codecs::gif::GifDecoder, AnimationDecoder, RgbImage};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::lsb;
+
+// How many decoded frames may sit in the channel between the decoder thread
+// and the embed/extract loop. Bounds memory on long animations instead of
+// decoding the whole GIF into a `Vec<Frame>` up front.
+const FRAME_CHANNEL_BOUND: usize = 4;
+
+// Upper bound on how much `FrameBitReader::read_bits` will eagerly reserve
+// for a declared payload length before it's actually decoded any of it. A
+// genuine payload larger than this still works fine (the `Vec` just grows
+// normally as bytes are pushed); this only caps the size of the single
+// speculative up-front reservation.
+const MAX_EAGER_CAPACITY: usize = 1 << 20; // 1 MiB
+
+// Fixed 256-color palette every frame is quantized against, instead of
+// letting the encoder pick (and re-pick) one per frame. Quantizing against a
+// palette that never changes means a pixel's palette index is a pure,
+// deterministic function of its RGB value, so embedding bits in the index
+// byte and decoding the palette back to RGB round-trips exactly.
+fn fixed_palette() -> Vec<u8> {
+    let mut palette = Vec::with_capacity(256 * 3);
+    for r in 0..8u16 {
+        for g in 0..8u16 {
+            for b in 0..4u16 {
+                palette.push((r * 255 / 7) as u8);
+                palette.push((g * 255 / 7) as u8);
+                palette.push((b * 255 / 3) as u8);
+            }
+        }
+    }
+    palette
+}
+
+// Nearest of `levels` evenly-spaced steps (0..=255) to `value`.
+fn nearest_level(value: u8, levels: u8) -> u8 {
+    let max_step = (levels - 1) as u32;
+    (((value as u32) * max_step + 127) / 255) as u8
+}
+
+// Index into `fixed_palette()` of the color nearest `pixel`. The palette
+// is a uniform 8x8x4 grid built in the same r/g/b nesting order, so the
+// nearest entry is a direct closed-form computation rather than a search.
+fn palette_index_for(pixel: [u8; 3]) -> u8 {
+    let r = nearest_level(pixel[0], 8);
+    let g = nearest_level(pixel[1], 8);
+    let b = nearest_level(pixel[2], 4);
+    r * 32 + g * 4 + b
+}
+
+pub fn is_animated_carrier(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(std::ffi::OsStr::to_str)
+        .map(|ext| ext.eq_ignore_ascii_case("gif"))
+        .unwrap_or(false)
+}
+
+fn mask_for(n: u8) -> u8 {
+    if n >= 8 {
+        0
+    } else {
+        0xFFu8 << n
+    }
+}
+
+// Spawn a background thread decoding `path` frame-by-frame, sending each
+// frame's RGB buffer (alpha is dropped; LSB hiding only touches color
+// channels) down a bounded channel so the caller never holds the whole
+// animation in memory at once.
+fn stream_decode(path: String) -> mpsc::Receiver<Result<RgbImage, String>> {
+    let (tx, rx) = mpsc::sync_channel(FRAME_CHANNEL_BOUND);
+
+    thread::spawn(move || {
+        let decoder = File::open(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|f| GifDecoder::new(BufReader::new(f)).map_err(|e| e.to_string()));
+
+        let decoder = match decoder {
+            Ok(d) => d,
+            Err(e) => {
+                let _ = tx.send(Err(e));
+                return;
+            }
+        };
+
+        for frame in decoder.into_frames() {
+            let msg = frame
+                .map(|f| image::DynamicImage::ImageRgba8(f.into_buffer()).to_rgb8())
+                .map_err(|e| e.to_string());
+            if tx.send(msg).is_err() {
+                return; // embed/extract loop stopped early, no need to keep decoding
+            }
+        }
+    });
+
+    rx
+}
+
+// Embed `stream` (a payload header's bytes followed by its payload bytes)
+// across the frames of an animated GIF decoy, continuing into the next
+// frame whenever the current one fills up. The leading `header_bytes` bytes
+// are packed at 1 bit/channel, same as a still-image carrier, so the header
+// stays readable before `bits_per_channel` is known; the rest is packed at
+// `bits_per_channel`.
+//
+// Bits are embedded into the low bits of each pixel's *palette index* byte,
+// not its RGB channels: `image::codecs::gif::GifEncoder` re-quantizes any
+// RGBA frame it's given through the `gif` crate's NeuQuant palette builder,
+// which would silently remap embedded low bits to whatever the nearest of
+// ≤256 colors turns out to be. Quantizing against our own fixed palette up
+// front and writing already-indexed frames through the raw `gif` crate
+// bypasses that re-quantization entirely.
+//
+// The encoded GIF is assembled in a temp file next to `output_path` and only
+// renamed into place once every bit of `stream` has been written, so a decoy
+// animation that runs out of frames mid-payload never leaves a truncated or
+// clobbered file at `output_path`.
+pub fn embed_stream(
+    decoy_path: &str,
+    output_path: &str,
+    stream: &[u8],
+    header_bytes: usize,
+    bits_per_channel: u8,
+) -> Result<(), Box<dyn Error>> {
+    lsb::validate_bits(bits_per_channel)?;
+
+    let palette = fixed_palette();
+    let mut rx = stream_decode(decoy_path.to_string());
+
+    let tmp_path = format!("{}.kersteg-tmp", output_path);
+    let result = (|| -> Result<(), Box<dyn Error>> {
+        // The GIF logical screen size has to be known before the encoder can
+        // be constructed, but it's only known once the first frame arrives,
+        // so pull it ahead of the loop instead of defaulting to 0x0 (which
+        // would make every decoder, including our own, reject the frames
+        // written below as out of bounds).
+        let first_frame = match rx.recv() {
+            Ok(frame) => frame?,
+            Err(_) => return Err("Error: decoy animation has no frames.".into()),
+        };
+        let (width, height) = first_frame.dimensions();
+
+        let tmp_file = File::create(&tmp_path)?;
+        let mut encoder = gif::Encoder::new(tmp_file, width as u16, height as u16, &palette)?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+
+        let total_bits = stream.len() as u64 * 8;
+        let header_bits = header_bytes as u64 * 8;
+        let mut bit_pos = 0u64;
+        let mut wrote_any_frame = false;
+
+        let remaining_frames = rx.into_iter().map(|frame| frame.map_err(|e| -> Box<dyn Error> { e.into() }));
+        for frame in std::iter::once(Ok(first_frame)).chain(remaining_frames) {
+            let frame = frame?;
+            let (width, height) = frame.dimensions();
+            wrote_any_frame = true;
+
+            let mut indices: Vec<u8> = frame
+                .pixels()
+                .map(|p| palette_index_for(p.0))
+                .collect();
+
+            for index in indices.iter_mut() {
+                if bit_pos >= total_bits {
+                    break;
+                }
+                let n = if bit_pos < header_bits { 1 } else { bits_per_channel };
+                let bits_here = (total_bits - bit_pos).min(n as u64) as u8;
+
+                let mut value = 0u8;
+                for i in 0..bits_here as u64 {
+                    let global_bit = bit_pos + i;
+                    let byte = stream[(global_bit / 8) as usize];
+                    let bit = (byte >> (7 - (global_bit % 8) as u8)) & 1;
+                    value = (value << 1) | bit;
+                }
+                value <<= n - bits_here;
+
+                *index = (*index & mask_for(n)) | value;
+                bit_pos += bits_here as u64;
+            }
+
+            let mut gif_frame = gif::Frame::from_indexed_pixels(width as u16, height as u16, &indices, None);
+            gif_frame.dispose = gif::DisposalMethod::Keep;
+            encoder.write_frame(&gif_frame)?;
+        }
+
+        if !wrote_any_frame {
+            return Err("Error: decoy animation has no frames.".into());
+        }
+        if bit_pos < total_bits {
+            return Err("Error: decoy animation ran out of frames before the payload fit.".into());
+        }
+
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => {
+            fs::rename(&tmp_path, output_path)?;
+            Ok(())
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&tmp_path);
+            Err(e)
+        }
+    }
+}
+
+// Stateful single-pass reader over an animated GIF carrier's embedded bits.
+// Holds one `stream_decode` thread/channel open and serves successive
+// `read_bits` calls against a continuously advancing cursor, so reading the
+// header, then a filename byte-by-byte, then the payload body only ever
+// decodes each frame once — instead of every call re-opening the GIF and
+// re-decoding from frame 0.
+pub struct FrameBitReader {
+    rx: mpsc::Receiver<Result<RgbImage, String>>,
+    current_indices: Vec<u8>,
+    pos_in_frame: usize,
+}
+
+impl FrameBitReader {
+    pub fn new(path: &str) -> Self {
+        FrameBitReader { rx: stream_decode(path.to_string()), current_indices: Vec::new(), pos_in_frame: 0 }
+    }
+
+    // Pull the next frame off the channel and re-derive its palette indices
+    // once the current frame is exhausted.
+    fn ensure_frame(&mut self) -> Result<bool, Box<dyn Error>> {
+        if self.pos_in_frame < self.current_indices.len() {
+            return Ok(true);
+        }
+        match self.rx.recv() {
+            Ok(frame) => {
+                let frame = frame?;
+                self.current_indices = frame.pixels().map(|p| palette_index_for(p.0)).collect();
+                self.pos_in_frame = 0;
+                Ok(true)
+            }
+            Err(_) => Ok(false), // decoder thread is done, no more frames
+        }
+    }
+
+    fn next_index(&mut self) -> Result<Option<u8>, Box<dyn Error>> {
+        if !self.ensure_frame()? {
+            return Ok(None);
+        }
+        let index = self.current_indices[self.pos_in_frame];
+        self.pos_in_frame += 1;
+        Ok(Some(index))
+    }
+
+    // Read `num_bytes` bytes packed at `n` bits/palette-index, continuing
+    // from wherever the previous `read_bits` call left off.
+    //
+    // `num_bytes` comes straight out of an untrusted carrier's header, and
+    // frames stream in lazily so there's no carrier-wide capacity to check it
+    // against up front. Rather than pre-sizing a `num_bytes`-long `Vec` (a
+    // hostile header claiming gigabytes would try to allocate that much
+    // before a single frame is even read), bytes are pushed onto a normally-
+    // growing `Vec` as they're decoded, so memory use is bounded by how much
+    // the carrier actually has, not by what the header claims.
+    pub fn read_bits(&mut self, num_bytes: usize, n: u8) -> Result<Vec<u8>, Box<dyn Error>> {
+        lsb::validate_bits(n)?;
+        let total_bits = (num_bytes as u64)
+            .checked_mul(8)
+            .ok_or("Error: declared payload length is too large to address.")?;
+
+        let mut out = Vec::with_capacity(num_bytes.min(MAX_EAGER_CAPACITY));
+        let mut current_byte = 0u8;
+        let mut bits_in_byte = 0u8;
+        let mut bit_pos = 0u64;
+
+        while bit_pos < total_bits {
+            let index = self
+                .next_index()?
+                .ok_or("Error: carrier ran out of frames before the declared payload length was reached.")?;
+
+            let bits_here = (total_bits - bit_pos).min(n as u64) as u8;
+            for i in 0..bits_here {
+                let bit = (index >> (n - 1 - i)) & 1;
+                current_byte = (current_byte << 1) | bit;
+                bits_in_byte += 1;
+                if bits_in_byte == 8 {
+                    out.push(current_byte);
+                    current_byte = 0;
+                    bits_in_byte = 0;
+                }
+            }
+            bit_pos += bits_here as u64;
+        }
+
+        Ok(out)
+    }
+}
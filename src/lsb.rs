@@ -0,0 +1,190 @@
+use image::RgbImage;
+use rayon::prelude::*;
+use std::error::Error;
+
+pub(crate) fn validate_bits(n: u8) -> Result<(), Box<dyn Error>> {
+    if !(1..=8).contains(&n) {
+        return Err(format!("Error: bits-per-channel must be between 1 and 8, got {}.", n).into());
+    }
+    Ok(())
+}
+
+// Number of `n`-bit channels needed to hold `num_bytes` bytes, rounded up,
+// along with the total bit count that produced it (callers need both, and
+// recomputing `total_bits` afterwards would just repeat this same checked
+// multiply for no reason).
+//
+// `num_bytes` is untrusted (it ultimately comes from a carrier's own header),
+// so every step is checked: a `num_bytes` close to `u64::MAX` would otherwise
+// overflow the `* 8` into a tiny, wrong channel count that passes the
+// capacity check below and only blows up as a huge allocation much later.
+fn channels_needed_for(num_bytes: usize, n: u8) -> Result<(usize, u64), Box<dyn Error>> {
+    let total_bits = (num_bytes as u64)
+        .checked_mul(8)
+        .ok_or("Error: payload is too large to address.")?;
+    let channels = total_bits
+        .checked_add(n as u64 - 1)
+        .ok_or("Error: payload is too large to address.")?
+        / n as u64;
+    let channels = usize::try_from(channels).map_err(|_| "Error: payload is too large to address.")?;
+    Ok((channels, total_bits))
+}
+
+// `start_channel + channels_needed`, checked: a sufficiently large (but
+// individually valid) `channels_needed` plus a nonzero `start_channel` can
+// still overflow `usize` even though `channels_needed_for` already checked
+// the multiply/divide that produced it.
+fn end_channel(start_channel: usize, channels_needed: usize) -> Result<usize, Box<dyn Error>> {
+    start_channel
+        .checked_add(channels_needed)
+        .ok_or_else(|| "Error: payload is too large to address.".into())
+}
+
+// Embed `data` into the low `n` bits of each channel in `raw`, starting at
+// channel `start_channel`. Returns the number of channels consumed.
+//
+// Larger `n` quadruples (at n=4) or further multiplies the carrier's capacity
+// at the cost of perturbing more bits of every decoy pixel, making the
+// stego carrier more visibly different from the original decoy.
+//
+// Every channel in the written region is written by exactly one rayon task,
+// so there's no shared state to lock: each task reads from the immutable
+// `data` slice and owns one disjoint byte of `raw`.
+pub fn embed_region(raw: &mut [u8], start_channel: usize, data: &[u8], n: u8) -> Result<usize, Box<dyn Error>> {
+    validate_bits(n)?;
+    let (channels_needed, total_bits) = channels_needed_for(data.len(), n)?;
+    let end = end_channel(start_channel, channels_needed)?;
+    if end > raw.len() {
+        return Err(format!(
+            "Error: payload needs {} channels at {} bit(s)/channel but only {} remain.",
+            channels_needed,
+            n,
+            raw.len().saturating_sub(start_channel)
+        )
+        .into());
+    }
+
+    let mask: u8 = if n >= 8 { 0 } else { 0xFFu8 << n };
+    let region = &mut raw[start_channel..end];
+
+    region.par_iter_mut().enumerate().for_each(|(local_idx, channel)| {
+        let bit_start = local_idx as u64 * n as u64;
+        let bits_here = ((total_bits - bit_start).min(n as u64)) as u8;
+
+        let mut value = 0u8;
+        for i in 0..bits_here as u64 {
+            let global_bit = bit_start + i;
+            let data_byte = data[(global_bit / 8) as usize];
+            let bit = (data_byte >> (7 - (global_bit % 8) as u8)) & 1;
+            value = (value << 1) | bit;
+        }
+        value <<= n - bits_here;
+
+        *channel = (*channel & mask) | value;
+    });
+
+    Ok(channels_needed)
+}
+
+// Extract `num_bytes` bytes from the low `n` bits of each channel in `raw`,
+// starting at channel `start_channel`.
+//
+// Parallelized by output byte rather than by channel: `raw` is only read,
+// so overlapping channel reads across tasks are safe, while every task still
+// owns a disjoint byte of the output `Vec`.
+pub fn extract_region(raw: &[u8], start_channel: usize, num_bytes: usize, n: u8) -> Result<Vec<u8>, Box<dyn Error>> {
+    validate_bits(n)?;
+    let (channels_needed, _total_bits) = channels_needed_for(num_bytes, n)?;
+    let end = end_channel(start_channel, channels_needed)?;
+    if end > raw.len() {
+        return Err(format!(
+            "Error: tried to read {} channels at {} bit(s)/channel but only {} remain.",
+            channels_needed,
+            n,
+            raw.len().saturating_sub(start_channel)
+        )
+        .into());
+    }
+
+    let mut data = vec![0u8; num_bytes];
+    data.par_iter_mut().enumerate().for_each(|(byte_idx, out_byte)| {
+        let mut value = 0u8;
+        for bit_in_byte in 0..8u64 {
+            let global_bit = byte_idx as u64 * 8 + bit_in_byte;
+            let channel = start_channel + (global_bit / n as u64) as usize;
+            let offset_in_channel = (global_bit % n as u64) as u8;
+            let bit = (raw[channel] >> (n - 1 - offset_in_channel)) & 1;
+            value = (value << 1) | bit;
+        }
+        *out_byte = value;
+    });
+    Ok(data)
+}
+
+// Embed `data` across the whole image at a fixed 1 bit/channel. Used for the
+// header region, which must be readable without already knowing the
+// configured bits-per-channel.
+pub fn embed_bytes(decoy: &RgbImage, data: &[u8]) -> Result<RgbImage, Box<dyn Error>> {
+    let (width, height) = decoy.dimensions();
+    let mut raw = decoy.as_raw().clone();
+    embed_region(&mut raw, 0, data, 1)?;
+    RgbImage::from_raw(width, height, raw).ok_or_else(|| "Failed to create image from raw data.".into())
+}
+
+// Extract `num_bytes` bytes at a fixed 1 bit/channel, starting `start_byte`
+// bytes into the bitstream.
+pub fn extract_bytes(encoded: &RgbImage, start_byte: usize, num_bytes: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    extract_region(encoded.as_raw(), start_byte * 8, num_bytes, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_at_every_bit_depth() {
+        let data = b"the quick brown fox jumps over the lazy dog";
+        for n in 1..=8u8 {
+            let mut raw = vec![0u8; data.len() * 8]; // plenty of channels at any depth
+            embed_region(&mut raw, 0, data, n).unwrap();
+            let recovered = extract_region(&raw, 0, data.len(), n).unwrap();
+            assert_eq!(&recovered, data, "round trip failed at {} bit(s)/channel", n);
+        }
+    }
+
+    #[test]
+    fn embed_region_rejects_out_of_range_bit_depth() {
+        let mut raw = vec![0u8; 64];
+        assert!(embed_region(&mut raw, 0, b"x", 0).is_err());
+        assert!(embed_region(&mut raw, 0, b"x", 9).is_err());
+    }
+
+    #[test]
+    fn embed_region_rejects_insufficient_capacity() {
+        let mut raw = vec![0u8; 4];
+        assert!(embed_region(&mut raw, 0, b"too much data for four channels", 1).is_err());
+    }
+
+    #[test]
+    fn embed_region_preserves_high_bits_outside_the_region() {
+        let mut raw = vec![0xFFu8; 16];
+        embed_region(&mut raw, 4, &[0b0000_0000], 1).unwrap();
+        // Channels before/after the written region are untouched.
+        assert_eq!(&raw[0..4], &[0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(&raw[12..16], &[0xFF, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn extract_region_rejects_a_num_bytes_that_would_overflow_bit_math() {
+        let raw = vec![0u8; 64];
+        assert!(extract_region(&raw, 0, usize::MAX, 1).is_err());
+    }
+
+    #[test]
+    fn extract_region_rejects_a_start_channel_plus_channels_needed_overflow() {
+        let raw = vec![0u8; 64];
+        // channels_needed_for(usize::MAX / 8, 1) succeeds on its own (it is
+        // usize::MAX - 7), but adding a start_channel of 8 to it overflows usize.
+        assert!(extract_region(&raw, 8, usize::MAX / 8, 1).is_err());
+    }
+}
@@ -1,15 +1,69 @@
-use std::env;
-use std::fs;
-use std::sync::{Arc, Mutex};
+mod crypto;
+mod frames;
+mod lsb;
+mod output;
+mod payload;
+
 use std::error::Error;
-use rayon::prelude::*;
+use std::fs;
+
+use clap::{Args, Parser, Subcommand};
 use image::io::Reader as ImageReader;
-use image::{Pixel, Rgb, RgbImage};
+use image::RgbImage;
+use payload::Payload;
+
+#[derive(Parser)]
+#[command(name = "kersteg", about = "Hide and recover payloads inside images via LSB steganography")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
-// Constants for encoding and decoding
-const MASK_ENCODING: u8 = 0b1111_1110;
-const MASK_DECODING: u8 = 0b0000_0001;
-const SHIFT: u8 = 7;
+#[derive(Subcommand)]
+enum Command {
+    /// Hide a payload (image, text, or file) inside a decoy image
+    Encode {
+        /// Decoy carrier image
+        decoy: String,
+        /// Output path for the steganographic image
+        output: String,
+        #[command(flatten)]
+        payload: PayloadArg,
+        /// Encrypt the payload with AES-256-GCM using this passphrase before embedding
+        #[arg(long)]
+        passphrase: Option<String>,
+        /// Bits per color channel to use for the payload body (1-8). Higher
+        /// values multiply capacity (n=4 quadruples it) at the cost of more
+        /// visible distortion in the carrier image.
+        #[arg(long, default_value_t = 1)]
+        bits: u8,
+        /// Skip the lossless PNG re-optimization pass for faster encoding
+        #[arg(long)]
+        no_optimize: bool,
+    },
+    /// Extract a hidden payload from a steganographic image
+    Decode {
+        /// Steganographic image to decode
+        input: String,
+        /// Passphrase to decrypt the payload, if it was embedded with one
+        #[arg(long)]
+        passphrase: Option<String>,
+    },
+}
+
+#[derive(Args)]
+#[group(required = true, multiple = false)]
+struct PayloadArg {
+    /// Hide another image inside the decoy
+    #[arg(long)]
+    secret_image: Option<String>,
+    /// Hide a short text message
+    #[arg(long)]
+    text: Option<String>,
+    /// Hide an arbitrary file
+    #[arg(long)]
+    file: Option<String>,
+}
 
 // Function to load an image from a file
 fn load_image(path: &str) -> Result<RgbImage, Box<dyn Error>> {
@@ -21,108 +75,74 @@ fn load_image(path: &str) -> Result<RgbImage, Box<dyn Error>> {
     Ok(img)
 }
 
-fn check_compatibility(secret_img: &RgbImage, decoy_img: &RgbImage) -> Result<(), Box<dyn Error>> {
-    if secret_img.dimensions() != decoy_img.dimensions() {
-        return Err("Error: Images must be the same size for LSB steganography.".into());
+fn build_payload(arg: &PayloadArg) -> Result<Payload, Box<dyn Error>> {
+    if let Some(path) = &arg.secret_image {
+        return Ok(Payload::Image(load_image(path)?));
     }
-
-    Ok(())
-}
-
-// Function to get the file extension, or return a default if none is found
-fn get_file_extension(file_path: &str) -> Result<&str, Box<dyn Error>> {
-    match std::path::Path::new(file_path).extension().and_then(std::ffi::OsStr::to_str) {
-        Some(extension) => Ok(extension),
-        None => Ok("png")
+    if let Some(text) = &arg.text {
+        return Ok(Payload::Text(text.clone()));
     }
-}
-
-
-// Function to process a pixel for LSB steganography, either encoding or decoding
-fn process_pixel(secret_pixel: Rgb<u8>, decoy_pixel: Rgb<u8>, encoding: bool) -> Rgb<u8> {
-    let mask = if encoding { MASK_ENCODING } else { MASK_DECODING };
-    let mut output_pixel = Rgb([0; 3]);
-
-    for i in 0..3 {
-        output_pixel[i] = if encoding {
-            // Hide secret image inside the decoy image
-            (decoy_pixel[i] & mask) | (secret_pixel[i] >> SHIFT)
-        } else {
-            // Extract the secret image from the encoded image
-            (secret_pixel[i] & mask) << SHIFT
-        }
+    if let Some(path) = &arg.file {
+        let filename = std::path::Path::new(path)
+            .file_name()
+            .and_then(std::ffi::OsStr::to_str)
+            .unwrap_or("payload.bin")
+            .to_string();
+        return Ok(Payload::File { filename, data: fs::read(path)? });
     }
-
-    output_pixel
-}
-
-// Function to perform LSB steganography
-fn perform_lsb_steganography(secret_img: &RgbImage, decoy_img: &RgbImage) -> Result<RgbImage, Box<dyn Error>> {
-    let (width, height) = secret_img.dimensions();
-    let output_img = Arc::new(Mutex::new(vec![0u8; (width * height * 3) as usize]));
-
-    let secret_pixels: Vec<_> = secret_img.pixels().cloned().collect();
-    let decoy_pixels: Vec<_> = decoy_img.pixels().cloned().collect();
-
-    secret_pixels.par_iter().enumerate().for_each(|(i, secret_pixel)| {
-        let decoy_pixel = &decoy_pixels[i];
-        let processed_pixel = process_pixel(*secret_pixel, *decoy_pixel, true);
-        let mut output = output_img.lock().unwrap();
-        output[i*3..i*3+3].copy_from_slice(&processed_pixel.channels());
-    });
-
-    let raw_output = Arc::try_unwrap(output_img).unwrap().into_inner()?;
-    RgbImage::from_raw(width, height, raw_output).ok_or("Failed to create image from raw data.".into())
-}
-
-// Function to decode a steganographic image
-fn decode_lsb_steganography(encoded_img: &RgbImage) -> Result<RgbImage, Box<dyn Error>> {
-    let (width, height) = encoded_img.dimensions();
-    let output_img = Arc::new(Mutex::new(vec![0u8; (width * height * 3) as usize]));
-
-    let encoded_pixels: Vec<_> = encoded_img.pixels().cloned().collect();
-
-    encoded_pixels.par_iter().enumerate().for_each(|(i, encoded_pixel)| {
-        let processed_pixel = process_pixel(*encoded_pixel, Rgb([0; 3]), false);
-        let mut output = output_img.lock().unwrap();
-        output[i*3..i*3+3].copy_from_slice(&processed_pixel.channels());
-    });
-
-    let raw_output = Arc::try_unwrap(output_img).unwrap().into_inner()?;
-    RgbImage::from_raw(width, height, raw_output).ok_or("Failed to create image from raw data.".into())
+    unreachable!("clap enforces exactly one payload source")
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let args: Vec<String> = env::args().collect();
-
-    match args.len() {
-        2 => {
-            let encoded_img = load_image(&args[1])?;
-            let decoded_img = decode_lsb_steganography(&encoded_img)?;
-            let file_extension = get_file_extension(&args[1])?;
-            decoded_img.save(format!("decoded_output.{}", file_extension))?;
-            println!("Decoding completed successfully.");
-        },
-        4 => {
-            let output_path = &args[3];
+    let cli = Cli::parse();
 
-            let secret_img = load_image(&args[1])?;
-            let decoy_img = load_image(&args[2])?;
-
-            check_compatibility(&secret_img, &decoy_img)?;
-            let steganographic_img = perform_lsb_steganography(&secret_img, &decoy_img)?;
-            steganographic_img.save(output_path)?;
+    match cli.command {
+        Command::Encode { decoy, output, payload, passphrase, bits, no_optimize } => {
+            let built_payload = build_payload(&payload)?;
 
+            // Animated carriers stream the payload across every frame and
+            // write the GIF directly; they bypass the still-image format
+            // gate and PNG optimization pass, which don't apply to GIF.
+            if frames::is_animated_carrier(&decoy) {
+                payload::embed_animated(&decoy, &output, &built_payload, passphrase.as_deref(), bits)?;
+            } else {
+                let decoy_img = load_image(&decoy)?;
+                let encoded_img = payload::embed(&decoy_img, &built_payload, passphrase.as_deref(), bits)?;
+                output::save(&encoded_img, &output, !no_optimize)?;
+            }
             println!("Encoding completed successfully.");
-        },
-        _ => {
-            if args.len() < 2 {
-                return Err("Too few arguments. Please provide either one encoded image (to be decoded), or three arguments for encoding (the secret image, the decoy image, and finally the output file path including the desired file type).".into());
+        }
+        Command::Decode { input, passphrase } => {
+            let decoded_payload = if frames::is_animated_carrier(&input) {
+                payload::extract_animated(&input, passphrase.as_deref())?
             } else {
-                return Err("Too many arguments. Please provide either one encoded image (to be decoded), or three arguments for encoding (the secret image, the decoy image, and finally the output file path including the desired file type).".into());
+                let encoded_img = load_image(&input)?;
+                payload::extract(&encoded_img, passphrase.as_deref())?
+            };
+
+            match decoded_payload {
+                Payload::Image(img) => {
+                    img.save("decoded_output.png")?;
+                    println!("Decoded an image to decoded_output.png");
+                }
+                Payload::Text(text) => {
+                    println!("Decoded text payload:\n{}", text);
+                }
+                Payload::File { filename, data } => {
+                    // The filename comes straight out of an untrusted carrier's
+                    // header bytes, so strip it down to a bare file name before
+                    // writing, same as the encode side already does for its
+                    // source file argument.
+                    let safe_name = std::path::Path::new(&filename)
+                        .file_name()
+                        .and_then(std::ffi::OsStr::to_str)
+                        .ok_or("Error: embedded filename is invalid.")?;
+                    fs::write(safe_name, data)?;
+                    println!("Decoded a file to {}", safe_name);
+                }
             }
-        },
+        }
     }
-    
+
     Ok(())
 }
@@ -0,0 +1,90 @@
+use image::RgbImage;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Container {
+    Png,
+    Bmp,
+    Qoi,
+    WebpLossless,
+}
+
+// Reject carrier formats that would quietly destroy every embedded LSB.
+fn lossless_container_from_extension(path: &str) -> Result<Container, Box<dyn Error>> {
+    let ext = Path::new(path)
+        .extension()
+        .and_then(std::ffi::OsStr::to_str)
+        .unwrap_or("")
+        .to_lowercase();
+
+    match ext.as_str() {
+        "png" => Ok(Container::Png),
+        "bmp" => Ok(Container::Bmp),
+        "qoi" => Ok(Container::Qoi),
+        "webp" => Ok(Container::WebpLossless),
+        "jpg" | "jpeg" => Err(format!(
+            "Error: {} would be saved as JPEG, a lossy format that destroys every embedded bit. Use a lossless container instead (.png, .bmp, .qoi, or lossless .webp).",
+            path
+        )
+        .into()),
+        other => Err(format!(
+            "Error: unsupported or unrecognized output format \".{}\". Use a lossless container instead (.png, .bmp, .qoi, or lossless .webp).",
+            other
+        )
+        .into()),
+    }
+}
+
+// Losslessly re-optimize an encoded PNG buffer (filter/deflate search) without
+// altering a single pixel byte, so the carrier stays small without disturbing
+// the hidden bits. Set `optimize` to false to skip this for speed.
+fn optimize_png(png_bytes: Vec<u8>, optimize: bool) -> Result<Vec<u8>, Box<dyn Error>> {
+    if !optimize {
+        return Ok(png_bytes);
+    }
+
+    let options = oxipng::Options::from_preset(4);
+    oxipng::optimize_from_memory(&png_bytes, &options)
+        .map_err(|e| format!("Error: PNG optimization failed: {}", e).into())
+}
+
+// Save `img` to `path`, rejecting lossy carrier formats and running a lossless
+// PNG re-optimization pass (unless `optimize` is false).
+pub fn save(img: &RgbImage, path: &str, optimize: bool) -> Result<(), Box<dyn Error>> {
+    match lossless_container_from_extension(path)? {
+        Container::Png => {
+            let mut png_bytes = Vec::new();
+            img.write_to(&mut std::io::Cursor::new(&mut png_bytes), image::ImageOutputFormat::Png)?;
+            let png_bytes = optimize_png(png_bytes, optimize)?;
+            fs::write(path, png_bytes)?;
+        }
+        Container::Bmp | Container::Qoi | Container::WebpLossless => {
+            img.save(path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_known_lossless_containers() {
+        assert_eq!(lossless_container_from_extension("out.png").unwrap(), Container::Png);
+        assert_eq!(lossless_container_from_extension("out.PNG").unwrap(), Container::Png);
+        assert_eq!(lossless_container_from_extension("out.bmp").unwrap(), Container::Bmp);
+        assert_eq!(lossless_container_from_extension("out.qoi").unwrap(), Container::Qoi);
+        assert_eq!(lossless_container_from_extension("out.webp").unwrap(), Container::WebpLossless);
+    }
+
+    #[test]
+    fn rejects_lossy_and_unknown_formats() {
+        assert!(lossless_container_from_extension("out.jpg").is_err());
+        assert!(lossless_container_from_extension("out.jpeg").is_err());
+        assert!(lossless_container_from_extension("out.gif").is_err());
+        assert!(lossless_container_from_extension("out").is_err());
+    }
+}
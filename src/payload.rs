@@ -0,0 +1,305 @@
+use std::error::Error;
+
+use crate::{crypto, frames, lsb};
+use image::RgbImage;
+
+// 4-byte magic tag identifying a kersteg payload header
+pub const MAGIC: [u8; 4] = *b"KSTG";
+// Fixed-size portion of the header: magic (4) + content type (1) + flags (1)
+// + bits-per-channel (1) + length (8). Always embedded at 1 bit/channel so it
+// can be read back before the payload's own bit-depth is known.
+const FIXED_HEADER_LEN: usize = MAGIC.len() + 1 + 1 + 1 + 8;
+// Flags byte bit indicating the payload bytes are AES-256-GCM ciphertext
+const FLAG_ENCRYPTED: u8 = 0b0000_0001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentType {
+    Image,
+    Text,
+    File,
+}
+
+impl ContentType {
+    fn to_byte(self) -> u8 {
+        match self {
+            ContentType::Image => 0,
+            ContentType::Text => 1,
+            ContentType::File => 2,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self, Box<dyn Error>> {
+        match byte {
+            0 => Ok(ContentType::Image),
+            1 => Ok(ContentType::Text),
+            2 => Ok(ContentType::File),
+            other => Err(format!("Error: unrecognized payload content-type byte {}.", other).into()),
+        }
+    }
+}
+
+// Header prepended to every embedded payload: a magic tag, the content type,
+// an encryption flag, the bits-per-channel the payload body is packed at,
+// the payload length, and (for files) the original filename.
+pub struct Header {
+    pub content_type: ContentType,
+    pub encrypted: bool,
+    pub bits_per_channel: u8,
+    pub filename: Option<String>,
+    pub payload_len: u64,
+}
+
+impl Header {
+    pub fn new(
+        content_type: ContentType,
+        encrypted: bool,
+        bits_per_channel: u8,
+        filename: Option<String>,
+        payload_len: u64,
+    ) -> Self {
+        Header { content_type, encrypted, bits_per_channel, filename, payload_len }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(FIXED_HEADER_LEN);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(self.content_type.to_byte());
+        bytes.push(if self.encrypted { FLAG_ENCRYPTED } else { 0 });
+        bytes.push(self.bits_per_channel);
+        bytes.extend_from_slice(&self.payload_len.to_le_bytes());
+        if let Some(name) = &self.filename {
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.push(0);
+        }
+        bytes
+    }
+}
+
+pub enum Payload {
+    Image(RgbImage),
+    Text(String),
+    File { filename: String, data: Vec<u8> },
+}
+
+// Serialize `payload` (optionally encrypting it) into a header followed by
+// its body bytes, ready to be streamed into a carrier's LSBs.
+fn build_stream(
+    payload: &Payload,
+    passphrase: Option<&str>,
+    bits_per_channel: u8,
+) -> Result<(Vec<u8>, Vec<u8>), Box<dyn Error>> {
+    let (content_type, filename, data) = match payload {
+        Payload::Image(img) => {
+            let mut png_bytes = Vec::new();
+            img.write_to(&mut std::io::Cursor::new(&mut png_bytes), image::ImageOutputFormat::Png)?;
+            (ContentType::Image, None, png_bytes)
+        }
+        Payload::Text(text) => (ContentType::Text, None, text.as_bytes().to_vec()),
+        Payload::File { filename, data } => (ContentType::File, Some(filename.clone()), data.clone()),
+    };
+
+    let (encrypted, data) = match passphrase {
+        Some(pass) => (true, crypto::encrypt(&data, pass)?),
+        None => (false, data),
+    };
+
+    let header = Header::new(content_type, encrypted, bits_per_channel, filename, data.len() as u64);
+    Ok((header.to_bytes(), data))
+}
+
+// Reassemble the `Payload` a header and its (already decrypted) body bytes describe.
+fn finish_payload(content_type: ContentType, filename: Option<String>, data: Vec<u8>) -> Result<Payload, Box<dyn Error>> {
+    Ok(match content_type {
+        ContentType::Image => Payload::Image(image::load_from_memory(&data)?.to_rgb8()),
+        ContentType::Text => Payload::Text(String::from_utf8(data)?),
+        ContentType::File => Payload::File { filename: filename.unwrap(), data },
+    })
+}
+
+// Embed `payload` into `decoy`, prefixed with its header, in raster order.
+// The header is always packed at 1 bit/channel; the payload body itself is
+// packed at `bits_per_channel` bits/channel. When `passphrase` is given, the
+// payload bytes are AES-256-GCM encrypted first.
+pub fn embed(
+    decoy: &RgbImage,
+    payload: &Payload,
+    passphrase: Option<&str>,
+    bits_per_channel: u8,
+) -> Result<RgbImage, Box<dyn Error>> {
+    let (header_bytes, data) = build_stream(payload, passphrase, bits_per_channel)?;
+
+    let (width, height) = decoy.dimensions();
+    let with_header = lsb::embed_bytes(decoy, &header_bytes)?;
+    let mut raw = with_header.into_raw();
+    lsb::embed_region(&mut raw, header_bytes.len() * 8, &data, bits_per_channel)?;
+
+    RgbImage::from_raw(width, height, raw).ok_or_else(|| "Failed to create image from raw data.".into())
+}
+
+// Read the header off `encoded`, then reconstruct exactly the payload it describes.
+// `passphrase` is required to decrypt payloads embedded with one.
+pub fn extract(encoded: &RgbImage, passphrase: Option<&str>) -> Result<Payload, Box<dyn Error>> {
+    let fixed = lsb::extract_bytes(encoded, 0, FIXED_HEADER_LEN)?;
+    if fixed[0..4] != MAGIC {
+        return Err("Error: no kersteg payload found (magic tag mismatch).".into());
+    }
+    let content_type = ContentType::from_byte(fixed[4])?;
+    let encrypted = fixed[5] & FLAG_ENCRYPTED != 0;
+    let bits_per_channel = fixed[6];
+    let payload_len = u64::from_le_bytes(fixed[7..15].try_into().unwrap()) as usize;
+
+    // Even at 8 bits/channel (the most generous packing), one payload byte
+    // takes at least one whole channel, so a carrier can never actually hold
+    // more payload bytes than it has channels. A corrupted or hostile header
+    // claiming more than that gets a clear capacity error here, before any
+    // filename or body bits are read.
+    if payload_len > encoded.as_raw().len() {
+        return Err("Error: declared payload length exceeds this carrier's capacity.".into());
+    }
+
+    let mut offset = FIXED_HEADER_LEN;
+    let filename = if content_type == ContentType::File {
+        let mut name_bytes = Vec::new();
+        loop {
+            let byte = lsb::extract_bytes(encoded, offset, 1)?[0];
+            offset += 1;
+            if byte == 0 {
+                break;
+            }
+            name_bytes.push(byte);
+        }
+        Some(String::from_utf8(name_bytes)?)
+    } else {
+        None
+    };
+
+    let data = lsb::extract_region(encoded.as_raw(), offset * 8, payload_len, bits_per_channel)?;
+    let data = if encrypted {
+        let pass = passphrase.ok_or("Error: this payload is encrypted; a passphrase is required.")?;
+        crypto::decrypt(&data, pass)?
+    } else {
+        data
+    };
+
+    finish_payload(content_type, filename, data)
+}
+
+// Animated-carrier counterpart of `embed`: streams the header and payload
+// body across every frame of `decoy_path` in sequence, writing the result to
+// `output_path` instead of returning an in-memory image.
+pub fn embed_animated(
+    decoy_path: &str,
+    output_path: &str,
+    payload: &Payload,
+    passphrase: Option<&str>,
+    bits_per_channel: u8,
+) -> Result<(), Box<dyn Error>> {
+    let (header_bytes, data) = build_stream(payload, passphrase, bits_per_channel)?;
+    let header_len = header_bytes.len();
+
+    let mut stream = header_bytes;
+    stream.extend_from_slice(&data);
+
+    frames::embed_stream(decoy_path, output_path, &stream, header_len, bits_per_channel)
+}
+
+// Animated-carrier counterpart of `extract`: decodes `path` frame-by-frame,
+// reading the header off the first frames before seeking into the body at
+// whatever bit-depth the header declares.
+pub fn extract_animated(path: &str, passphrase: Option<&str>) -> Result<Payload, Box<dyn Error>> {
+    let mut reader = frames::FrameBitReader::new(path);
+
+    let fixed = reader.read_bits(FIXED_HEADER_LEN, 1)?;
+    if fixed[0..4] != MAGIC {
+        return Err("Error: no kersteg payload found (magic tag mismatch).".into());
+    }
+    let content_type = ContentType::from_byte(fixed[4])?;
+    let encrypted = fixed[5] & FLAG_ENCRYPTED != 0;
+    let bits_per_channel = fixed[6];
+    let payload_len = u64::from_le_bytes(fixed[7..15].try_into().unwrap()) as usize;
+
+    let filename = if content_type == ContentType::File {
+        let mut name_bytes = Vec::new();
+        loop {
+            let byte = reader.read_bits(1, 1)?[0];
+            if byte == 0 {
+                break;
+            }
+            name_bytes.push(byte);
+        }
+        Some(String::from_utf8(name_bytes)?)
+    } else {
+        None
+    };
+
+    let data = reader.read_bits(payload_len, bits_per_channel)?;
+    let data = if encrypted {
+        let pass = passphrase.ok_or("Error: this payload is encrypted; a passphrase is required.")?;
+        crypto::decrypt(&data, pass)?
+    } else {
+        data
+    };
+
+    finish_payload(content_type, filename, data)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blank_decoy() -> RgbImage {
+        // Plenty of channels for a short payload at any bit depth.
+        RgbImage::from_pixel(64, 64, image::Rgb([0, 0, 0]))
+    }
+
+    #[test]
+    fn round_trips_a_text_payload_at_every_bit_depth() {
+        for n in 1..=8u8 {
+            let decoy = blank_decoy();
+            let payload = Payload::Text("the quick brown fox".to_string());
+            let encoded = embed(&decoy, &payload, None, n).unwrap();
+
+            match extract(&encoded, None).unwrap() {
+                Payload::Text(text) => assert_eq!(text, "the quick brown fox", "failed at {} bit(s)/channel", n),
+                _ => panic!("expected a text payload"),
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_an_encrypted_text_payload() {
+        let decoy = blank_decoy();
+        let payload = Payload::Text("a secret message".to_string());
+        let encoded = embed(&decoy, &payload, Some("hunter2"), 2).unwrap();
+
+        match extract(&encoded, Some("hunter2")).unwrap() {
+            Payload::Text(text) => assert_eq!(text, "a secret message"),
+            _ => panic!("expected a text payload"),
+        }
+
+        assert!(extract(&encoded, Some("wrong passphrase")).is_err());
+        assert!(extract(&encoded, None).is_err());
+    }
+
+    #[test]
+    fn round_trips_a_file_payload_and_keeps_its_filename() {
+        let decoy = blank_decoy();
+        let payload = Payload::File { filename: "notes.txt".to_string(), data: b"file contents".to_vec() };
+        let encoded = embed(&decoy, &payload, None, 1).unwrap();
+
+        match extract(&encoded, None).unwrap() {
+            Payload::File { filename, data } => {
+                assert_eq!(filename, "notes.txt");
+                assert_eq!(data, b"file contents");
+            }
+            _ => panic!("expected a file payload"),
+        }
+    }
+
+    #[test]
+    fn rejects_a_carrier_with_no_embedded_payload() {
+        let decoy = blank_decoy();
+        assert!(extract(&decoy, None).is_err());
+    }
+}